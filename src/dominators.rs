@@ -0,0 +1,207 @@
+extern crate petgraph;
+
+use self::petgraph::visit::IntoNeighborsDirected;
+use self::petgraph::Direction::{Incoming, Outgoing};
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Returns the nearest common dominator of `u` and `v` in `graph`, using
+/// immediate-dominator analysis rooted at `entry`.
+///
+/// The plain `lca` function only gives meaningful answers on tree-like
+/// graphs, returning `None` whenever a cycle is reachable. This instead
+/// computes immediate dominators with the iterative Cooper-Harvey-Kennedy
+/// fixpoint and walks the resulting dominator tree, so it gives a
+/// well-defined answer on arbitrary reducible DAGs with shared ancestry,
+/// a case `compare_ancestors` only partially handles.
+///
+/// Generic over petgraph's visitor traits rather than a concrete `Graph`,
+/// so it also runs on `StableGraph`, `GraphMap` and other graph adaptors.
+///
+/// * `graph` - Graph that the dominator analysis is applied on.
+/// * `entry` - The entry node dominance is computed from.
+/// * `u`     - The first node to find the nearest common dominator of.
+/// * `v`     - The second node to find the nearest common dominator of.
+///
+/// Returns `None` if `u` or `v` is not reachable from `entry`.
+pub fn lca_dominators<G>(graph: G, entry: G::NodeId, u: G::NodeId, v: G::NodeId) -> Option<G::NodeId>
+where
+    G: IntoNeighborsDirected,
+    G::NodeId: Eq + Hash,
+{
+    let (postorder, number) = postorder_numbers(graph, entry);
+    let idom = immediate_dominators(graph, entry, &postorder, &number);
+
+    if !idom.contains_key(&u) || !idom.contains_key(&v) {
+        return None;
+    }
+
+    Some(intersect(u, v, &idom, &number))
+}
+
+/// Runs a DFS from `entry` over `Outgoing` edges and returns the nodes in
+/// postorder along with each node's position in that order (a node always
+/// has a higher number than its descendants in the DFS tree, and `entry`
+/// the highest of all).
+fn postorder_numbers<G>(graph: G, entry: G::NodeId) -> (Vec<G::NodeId>, HashMap<G::NodeId, u32>)
+where
+    G: IntoNeighborsDirected,
+    G::NodeId: Eq + Hash,
+{
+    let mut visited = HashMap::<G::NodeId, bool>::new();
+    let mut postorder = Vec::new();
+    dfs_postorder(graph, entry, &mut visited, &mut postorder);
+
+    let mut number = HashMap::new();
+    for (i, &node) in postorder.iter().enumerate() {
+        number.insert(node, i as u32);
+    }
+    (postorder, number)
+}
+
+fn dfs_postorder<G>(
+    graph: G,
+    node: G::NodeId,
+    visited: &mut HashMap<G::NodeId, bool>,
+    postorder: &mut Vec<G::NodeId>,
+) where
+    G: IntoNeighborsDirected,
+    G::NodeId: Eq + Hash,
+{
+    if *visited.get(&node).unwrap_or(&false) {
+        return;
+    }
+    visited.insert(node, true);
+
+    for child in graph.neighbors_directed(node, Outgoing) {
+        dfs_postorder(graph, child, visited, postorder);
+    }
+    postorder.push(node);
+}
+
+/// Computes immediate dominators from `entry` using the iterative
+/// Cooper-Harvey-Kennedy fixpoint: process nodes in reverse postorder,
+/// intersecting the already-processed predecessors' dominator sets until
+/// nothing changes.
+fn immediate_dominators<G>(
+    graph: G,
+    entry: G::NodeId,
+    postorder: &[G::NodeId],
+    number: &HashMap<G::NodeId, u32>,
+) -> HashMap<G::NodeId, G::NodeId>
+where
+    G: IntoNeighborsDirected,
+    G::NodeId: Eq + Hash,
+{
+    let rpo = postorder.iter().rev().cloned().collect::<Vec<G::NodeId>>();
+
+    let mut idom = HashMap::new();
+    idom.insert(entry, entry);
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &node in rpo.iter() {
+            if node == entry {
+                continue;
+            }
+
+            let mut new_idom: Option<G::NodeId> = None;
+            for pred in graph.neighbors_directed(node, Incoming) {
+                if !idom.contains_key(&pred) {
+                    continue;
+                }
+                new_idom = Some(match new_idom {
+                    None => pred,
+                    Some(current) => intersect(current, pred, &idom, number),
+                });
+            }
+
+            if let Some(new_idom) = new_idom {
+                if idom.get(&node) != Some(&new_idom) {
+                    idom.insert(node, new_idom);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    idom
+}
+
+/// Walks two finger pointers up the `idom` tree toward the node with the
+/// larger postorder number until they meet, giving the nearest common
+/// dominator of `u` and `v`.
+fn intersect<Id: Eq + Hash + Copy>(
+    u: Id,
+    v: Id,
+    idom: &HashMap<Id, Id>,
+    number: &HashMap<Id, u32>,
+) -> Id {
+    let mut finger1 = u;
+    let mut finger2 = v;
+    while finger1 != finger2 {
+        while number[&finger1] < number[&finger2] {
+            finger1 = idom[&finger1];
+        }
+        while number[&finger2] < number[&finger1] {
+            finger2 = idom[&finger2];
+        }
+    }
+    finger1
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate petgraph;
+
+    use self::petgraph::graph::Graph;
+    use super::lca_dominators;
+
+    /// Tests that `lca_dominators` finds the nearest common dominator on
+    /// a DAG where two branches merge back together.
+    #[test]
+    fn testlcadominators_merge() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+        let n4 = map.add_node("4");
+        let n5 = map.add_node("5");
+
+        map.extend_with_edges(&[(n1, n2), (n1, n3), (n2, n4), (n3, n4), (n4, n5)]);
+
+        assert_eq!(Some(n1), lca_dominators(&map, n1, n2, n3));
+        assert_eq!(Some(n1), lca_dominators(&map, n1, n2, n4));
+        assert_eq!(Some(n4), lca_dominators(&map, n1, n4, n5));
+    }
+
+    /// Tests that `lca_dominators` still gives an answer on a graph with a
+    /// cycle reachable from the entry, unlike the plain `lca`.
+    #[test]
+    fn testlcadominators_cycle() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+
+        map.extend_with_edges(&[(n1, n2), (n2, n3), (n3, n2)]);
+
+        assert_eq!(Some(n2), lca_dominators(&map, n1, n2, n3));
+    }
+
+    /// Tests that `None` is returned when a node is unreachable from the
+    /// entry.
+    #[test]
+    fn testlcadominators_unreachable() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+
+        map.extend_with_edges(&[(n1, n2)]);
+
+        assert_eq!(None, lca_dominators(&map, n1, n2, n3));
+    }
+}
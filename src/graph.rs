@@ -1,77 +1,95 @@
 extern crate pathfinding;
 extern crate petgraph;
 
-use self::pathfinding::prelude::{astar, topological_sort};
-use self::petgraph::{graph::NodeIndex, Direction::Incoming, Direction::Outgoing, Graph};
+mod dominators;
+mod jump_table;
+mod lca_index;
+mod topo;
+mod union_find;
 
+use self::pathfinding::prelude::{astar, topological_sort};
+use self::petgraph::visit::{
+    EdgeRef, IntoEdgesDirected, IntoNeighborsDirected, NodeCount, NodeIndexable, VisitMap,
+    Visitable,
+};
+use self::petgraph::Direction::{Incoming, Outgoing};
+use self::union_find::UnionFind;
+
+pub use self::dominators::lca_dominators;
+pub use self::jump_table::JumpTable;
+pub use self::lca_index::LcaIndex;
+pub use self::topo::{validate, CycleError, TopoOrder};
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::collections::HashMap;
 use std::collections::LinkedList;
+use std::hash::Hash;
 
 /// Returns list of neighbors of a node.
-fn neighbors<N, E>(graph: &Graph<N, E>, n: NodeIndex) -> LinkedList<(NodeIndex)> {
-    graph
-        .neighbors_directed(n, Outgoing)
-        .collect::<LinkedList<NodeIndex>>()
+fn neighbors<G>(graph: G, n: G::NodeId) -> LinkedList<G::NodeId>
+where
+    G: IntoNeighborsDirected,
+{
+    graph.neighbors_directed(n, Outgoing).collect()
 }
 /// Returns list of neighbors of a node with the corresponding cost.
-fn neighbors_cost<N, E>(graph: &Graph<N, E>, n: NodeIndex) -> LinkedList<(NodeIndex, u32)> {
-    let mut list: LinkedList<(NodeIndex, u32)> = LinkedList::new();
-    let mut neighbors = graph
-        .neighbors_directed(n, Outgoing)
-        .collect::<LinkedList<NodeIndex>>();
-    for element in neighbors.iter_mut() {
-        list.push_back((*element, 1));
+fn neighbors_cost<G>(graph: G, n: G::NodeId) -> LinkedList<(G::NodeId, u32)>
+where
+    G: IntoNeighborsDirected,
+{
+    let mut list = LinkedList::new();
+    for element in graph.neighbors_directed(n, Outgoing) {
+        list.push_back((element, 1));
     }
     return list;
 }
 
-/// Puts the ancestors of `node` onto a HashMap
-fn ancestors<N, E>(graph: &Graph<N, E>, node: NodeIndex) -> HashMap<NodeIndex, bool> {
-    let ancestors = HashMap::<NodeIndex, bool>::new();
-    return add_ancestors(graph, ancestors, node);
+/// Puts the ancestors of `node` onto a `VisitMap`.
+fn ancestors<G>(graph: G, node: G::NodeId) -> G::Map
+where
+    G: IntoNeighborsDirected + Visitable,
+{
+    let mut ancestors = graph.visit_map();
+    add_ancestors(graph, &mut ancestors, node);
+    return ancestors;
 }
 
-/// Puts the ancestors of `node` onto the HashMap `ancestors`
-fn add_ancestors<N, E>(
-    graph: &Graph<N, E>,
-    mut ancestors: HashMap<NodeIndex, bool>,
-    node: NodeIndex,
-) -> HashMap<NodeIndex, bool> {
-    ancestors.insert(node, true);
-    let mut neighbors = graph
-        .neighbors_directed(node, Incoming)
-        .collect::<LinkedList<NodeIndex>>();
-    for element in neighbors.iter_mut() {
-        ancestors = add_ancestors(graph, ancestors, *element);
+/// Puts the ancestors of `node` onto the `VisitMap` `ancestors`.
+fn add_ancestors<G>(graph: G, ancestors: &mut G::Map, node: G::NodeId)
+where
+    G: IntoNeighborsDirected + Visitable,
+{
+    ancestors.visit(node);
+    for element in graph.neighbors_directed(node, Incoming) {
+        add_ancestors(graph, ancestors, element);
     }
-    return ancestors;
 }
 
 /// Compares ancestors of `node` with `ancestors` and returns the lowest common ancestor.
-fn compare_ancestors<N, E>(
-    graph: &Graph<N, E>,
-    ancestors: &HashMap<NodeIndex, bool>,
-    node: NodeIndex,
-) -> Option<NodeIndex> {
+fn compare_ancestors<G>(graph: G, ancestors: &G::Map, node: G::NodeId) -> Option<G::NodeId>
+where
+    G: IntoNeighborsDirected + Visitable,
+{
     return compare_ancestors_node(graph, ancestors, node, 0).0;
 }
 
 /// Compares ancestors of `node` with `ancestors` and returns the lowest common ancestor along with the cost.
-fn compare_ancestors_node<N, E>(
-    graph: &Graph<N, E>,
-    ancestors: &HashMap<NodeIndex, bool>,
-    node: NodeIndex,
+fn compare_ancestors_node<G>(
+    graph: G,
+    ancestors: &G::Map,
+    node: G::NodeId,
     cost: i32,
-) -> (Option<NodeIndex>, i32) {
-    if ancestors.contains_key(&node) {
+) -> (Option<G::NodeId>, i32)
+where
+    G: IntoNeighborsDirected + Visitable,
+{
+    if ancestors.is_visited(&node) {
         return (Some(node), cost);
     }
-    let mut neighbors = graph
-        .neighbors_directed(node, Incoming)
-        .collect::<LinkedList<NodeIndex>>();
     let mut lca_cost = (None, <i32>::max_value());
-    for element in neighbors.iter_mut() {
-        let ancestor_cost = compare_ancestors_node(&graph, ancestors, *element, cost + 1);
+    for element in graph.neighbors_directed(node, Incoming) {
+        let ancestor_cost = compare_ancestors_node(graph, ancestors, element, cost + 1);
         if ancestor_cost.0.is_some() && ancestor_cost.1 < lca_cost.1 {
             lca_cost = ancestor_cost;
         }
@@ -80,14 +98,15 @@ fn compare_ancestors_node<N, E>(
 }
 
 /// Check if there is a cycle in the graph.
-fn check_cycle<N, E>(graph: &Graph<N, E>, node: NodeIndex) -> bool {
-    let mut neighbors = graph
-        .neighbors_directed(node, Incoming)
-        .collect::<LinkedList<NodeIndex>>();
-    let mut visited = HashMap::<NodeIndex, bool>::new();
-    visited.insert(node, true);
-    for element in neighbors.iter_mut() {
-        if check_cycle_node(graph, visited.clone(), *element) {
+fn check_cycle<G>(graph: G, node: G::NodeId) -> bool
+where
+    G: IntoNeighborsDirected + Visitable,
+    G::Map: Clone,
+{
+    let mut visited = graph.visit_map();
+    visited.visit(node);
+    for element in graph.neighbors_directed(node, Incoming) {
+        if check_cycle_node(graph, visited.clone(), element) {
             return true;
         }
     }
@@ -95,20 +114,17 @@ fn check_cycle<N, E>(graph: &Graph<N, E>, node: NodeIndex) -> bool {
 }
 
 /// Check if there is a cycle in the graph by checking if a node is visited multiple times.
-fn check_cycle_node<N, E>(
-    graph: &Graph<N, E>,
-    mut visited: HashMap<NodeIndex, bool>,
-    node: NodeIndex,
-) -> bool {
-    if visited.contains_key(&node) {
+fn check_cycle_node<G>(graph: G, mut visited: G::Map, node: G::NodeId) -> bool
+where
+    G: IntoNeighborsDirected + Visitable,
+    G::Map: Clone,
+{
+    if visited.is_visited(&node) {
         return true;
     }
-    visited.insert(node, true);
-    let mut neighbors = graph
-        .neighbors_directed(node, Incoming)
-        .collect::<LinkedList<NodeIndex>>();
-    for element in neighbors.iter_mut() {
-        if check_cycle_node(graph, visited.clone(), *element) {
+    visited.visit(node);
+    for element in graph.neighbors_directed(node, Incoming) {
+        if check_cycle_node(graph, visited.clone(), element) {
             return true;
         }
     }
@@ -119,11 +135,18 @@ fn check_cycle_node<N, E>(
 ///
 /// This function calculates the lowest common ancestor of two nodes in a graph that is structured as a binary tree.
 ///
+/// Generic over petgraph's visitor traits rather than a concrete `Graph`,
+/// so it also runs directly on `StableGraph`, `GraphMap`, `Reversed<&G>`
+/// and other graph adaptors.
+///
 /// * `graph` - Graph that the lowest common ancestor is applied on.
-/// * `root`  - The root node of the binary tree.
 /// * `node1` - The first node to calculate lca.
 /// * `node2` - The second node to calculate lca.
-pub fn lca<N, E>(graph: &Graph<N, E>, node1: NodeIndex, node2: NodeIndex) -> Option<NodeIndex> {
+pub fn lca<G>(graph: G, node1: G::NodeId, node2: G::NodeId) -> Option<G::NodeId>
+where
+    G: IntoNeighborsDirected + Visitable + NodeIndexable + NodeCount,
+    G::Map: Clone,
+{
     if check_cycle(graph, node1) || check_cycle(graph, node2) {
         return None;
     }
@@ -131,10 +154,252 @@ pub fn lca<N, E>(graph: &Graph<N, E>, node1: NodeIndex, node2: NodeIndex) -> Opt
     return compare_ancestors(graph, &ancestors, node2);
 }
 
+/// Answers many lowest-common-ancestor queries at once using Tarjan's
+/// offline LCA algorithm.
+///
+/// This runs a single DFS from `root` over a union-find structure instead
+/// of reprocessing the whole ancestor set for every pair, so a batch of
+/// queries runs in near-linear total time rather than the per-query cost
+/// of repeatedly calling `lca`.
+///
+/// * `graph`   - Graph that the lowest common ancestor is applied on.
+/// * `root`    - The root node to run the single DFS from.
+/// * `queries` - Pairs of nodes to find the lowest common ancestor of.
+///
+/// Returns one answer per query, in the same order, or `None` for a pair
+/// where either node is unreachable from `root`.
+pub fn lca_all<G>(
+    graph: G,
+    root: G::NodeId,
+    queries: &[(G::NodeId, G::NodeId)],
+) -> Vec<Option<G::NodeId>>
+where
+    G: IntoNeighborsDirected + Visitable + NodeIndexable + NodeCount,
+    G::NodeId: Eq + Hash,
+{
+    let mut node_queries: HashMap<G::NodeId, LinkedList<(G::NodeId, usize)>> = HashMap::new();
+    for (i, &(u, v)) in queries.iter().enumerate() {
+        node_queries
+            .entry(u)
+            .or_insert_with(LinkedList::new)
+            .push_back((v, i));
+        node_queries
+            .entry(v)
+            .or_insert_with(LinkedList::new)
+            .push_back((u, i));
+    }
+
+    let mut uf = UnionFind::<G::NodeId>::new();
+    let mut in_progress = graph.visit_map();
+    let mut finished = graph.visit_map();
+    let mut answers: Vec<Option<G::NodeId>> = queries.iter().map(|_| None).collect();
+
+    tarjan_lca(
+        graph,
+        root,
+        &mut uf,
+        &mut in_progress,
+        &mut finished,
+        &node_queries,
+        &mut answers,
+    );
+    return answers;
+}
+
+/// Recursive step of Tarjan's offline LCA: visits `node`'s subtree,
+/// merging each child's set into `node`'s, then answers any pending
+/// queries involving `node` whose partner is already finished.
+///
+/// `lca_all` only gives meaningful answers when `graph` is a tree rooted
+/// at `root` (like the plain recursive `lca`); `in_progress` guards
+/// against a cycle turning this DFS into unbounded recursion, and
+/// `finished` additionally guards against unioning a node reached via a
+/// second parent into the wrong set — a node already finished belongs to
+/// whichever branch first reached it, so a later parent treats the edge
+/// to it as a non-tree edge and leaves it alone instead of merging.
+fn tarjan_lca<G>(
+    graph: G,
+    node: G::NodeId,
+    uf: &mut UnionFind<G::NodeId>,
+    in_progress: &mut G::Map,
+    finished: &mut G::Map,
+    node_queries: &HashMap<G::NodeId, LinkedList<(G::NodeId, usize)>>,
+    answers: &mut Vec<Option<G::NodeId>>,
+) where
+    G: IntoNeighborsDirected + Visitable,
+    G::NodeId: Eq + Hash,
+{
+    if finished.is_visited(&node) || in_progress.is_visited(&node) {
+        return;
+    }
+    in_progress.visit(node);
+
+    uf.make_set(node);
+    uf.set_ancestor(node, node);
+
+    for child in graph.neighbors_directed(node, Outgoing) {
+        if finished.is_visited(&child) || in_progress.is_visited(&child) {
+            continue;
+        }
+        tarjan_lca(graph, child, uf, in_progress, finished, node_queries, answers);
+        uf.union(node, child);
+        uf.set_ancestor(node, node);
+    }
+
+    finished.visit(node);
+
+    if let Some(partners) = node_queries.get(&node) {
+        for &(partner, idx) in partners.iter() {
+            if finished.is_visited(&partner) {
+                answers[idx] = Some(uf.ancestor(partner));
+            }
+        }
+    }
+}
+
+/// Returns the lowest common ancestor of `u` and `v` together with the
+/// minimum total path cost `u -> lca -> v`, using real edge weights.
+///
+/// Computes the candidate ancestor set of `u` with accumulated shortest
+/// distances via Dijkstra over `Incoming` edges, then does the same for
+/// `v` and, among nodes reachable as an ancestor of both, picks the one
+/// minimizing `dist_u + dist_v`. This is the weighted counterpart to
+/// `compare_ancestors_node`'s unweighted cost, using `edge_cost` instead
+/// of the hard-coded `1` in `neighbors_cost`.
+///
+/// Generic over petgraph's visitor traits rather than a concrete `Graph`,
+/// so it also runs directly on `StableGraph`, `GraphMap` and other graph
+/// adaptors.
+///
+/// * `graph`     - Graph that the lowest common ancestor is applied on.
+/// * `u`         - The first node to calculate lca.
+/// * `v`         - The second node to calculate lca.
+/// * `edge_cost` - Returns the weight of an edge.
+pub fn lca_weighted<G>(
+    graph: G,
+    u: G::NodeId,
+    v: G::NodeId,
+    edge_cost: impl Fn(G::EdgeRef) -> u32,
+) -> Option<(G::NodeId, u32)>
+where
+    G: IntoEdgesDirected,
+    G::NodeId: Eq + Hash + Ord + Copy,
+{
+    let dist_u = ancestor_distances(graph, u, &edge_cost);
+    let dist_v = ancestor_distances(graph, v, &edge_cost);
+
+    let mut best: Option<(G::NodeId, u32)> = None;
+    for (&node, &dv) in dist_v.iter() {
+        if let Some(&du) = dist_u.get(&node) {
+            let total = du + dv;
+            if best.map_or(true, |(_, best_cost)| total < best_cost) {
+                best = Some((node, total));
+            }
+        }
+    }
+    best
+}
+
+/// Runs Dijkstra from `start` over `Incoming` edges, returning the
+/// shortest accumulated `edge_cost` distance to every ancestor.
+fn ancestor_distances<G>(
+    graph: G,
+    start: G::NodeId,
+    edge_cost: &impl Fn(G::EdgeRef) -> u32,
+) -> HashMap<G::NodeId, u32>
+where
+    G: IntoEdgesDirected,
+    G::NodeId: Eq + Hash + Ord + Copy,
+{
+    let mut dist = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    dist.insert(start, 0u32);
+    queue.push(Reverse((0u32, start)));
+
+    while let Some(Reverse((cost, node))) = queue.pop() {
+        if cost > *dist.get(&node).unwrap_or(&u32::max_value()) {
+            continue;
+        }
+        for edge in graph.edges_directed(node, Incoming) {
+            let next = edge.source();
+            let next_cost = cost + edge_cost(edge);
+            if next_cost < *dist.get(&next).unwrap_or(&u32::max_value()) {
+                dist.insert(next, next_cost);
+                queue.push(Reverse((next_cost, next)));
+            }
+        }
+    }
+    dist
+}
+
+/// A lowest common ancestor function that reuses a precomputed
+/// `TopoOrder` to bound the ancestor walk, instead of independently
+/// recursing up `node1`'s and `node2`'s full ancestor chains the way
+/// `lca` does.
+///
+/// `validate` already proved the graph acyclic once, so any common
+/// ancestor of `node1` and `node2` must sit at or before
+/// `max(topo.position(node1), topo.position(node2))` in the topological
+/// order; this sweeps only that prefix, in reverse, propagating an
+/// "is an ancestor of `node1`"/"of `node2`" flag from each node to its
+/// predecessors and stopping at the first node (highest position) where
+/// both flags are set. This also skips the per-call cycle-check cost
+/// `lca` pays for both `node1` and `node2`.
+///
+/// * `graph`  - Graph that the lowest common ancestor is applied on.
+/// * `topo`   - Topological order produced by `validate` for this graph.
+/// * `node1`  - The first node to calculate lca.
+/// * `node2`  - The second node to calculate lca.
+pub fn lca_checked<G>(
+    graph: G,
+    topo: &TopoOrder<G::NodeId>,
+    node1: G::NodeId,
+    node2: G::NodeId,
+) -> Option<G::NodeId>
+where
+    G: IntoNeighborsDirected,
+    G::NodeId: Eq + Hash + Copy,
+{
+    let pos1 = topo.position(node1)?;
+    let pos2 = topo.position(node2)?;
+    let bound = pos1.max(pos2);
+
+    let mut is_ancestor1: HashMap<G::NodeId, bool> = HashMap::new();
+    let mut is_ancestor2: HashMap<G::NodeId, bool> = HashMap::new();
+    is_ancestor1.insert(node1, true);
+    is_ancestor2.insert(node2, true);
+
+    for &node in topo.order()[..=bound].iter().rev() {
+        let is_anc1 = *is_ancestor1.get(&node).unwrap_or(&false);
+        let is_anc2 = *is_ancestor2.get(&node).unwrap_or(&false);
+        if is_anc1 && is_anc2 {
+            return Some(node);
+        }
+        if is_anc1 || is_anc2 {
+            for pred in graph.neighbors_directed(node, Incoming) {
+                if is_anc1 {
+                    is_ancestor1.insert(pred, true);
+                }
+                if is_anc2 {
+                    is_ancestor2.insert(pred, true);
+                }
+            }
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
+    extern crate petgraph;
+
+    use self::petgraph::graph::{EdgeReference, Graph};
     use super::lca;
-    use super::Graph;
+    use super::lca_all;
+    use super::lca_checked;
+    use super::lca_weighted;
+    use super::validate;
+    use super::EdgeRef;
 
     /// Tests normal operations of lca on a connected graph structured as an directed acyclic graph.
     #[test]
@@ -281,4 +546,130 @@ mod tests {
         assert_eq!(false, lca(&map, n2, n6).is_some());
         assert_eq!(false, lca(&map, n6, n7).is_some());
     }
+
+    /// Tests that `lca_all` answers a batch of queries on a rooted tree in
+    /// a single pass, matching what calling `lca` for each pair would give.
+    #[test]
+    fn testlcaall_batch() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+        let n4 = map.add_node("4");
+        let n5 = map.add_node("5");
+        let n6 = map.add_node("6");
+        let n7 = map.add_node("7");
+        let n8 = map.add_node("8");
+
+        map.extend_with_edges(&[
+            (n1, n2),
+            (n1, n3),
+            (n2, n4),
+            (n2, n5),
+            (n3, n6),
+            (n5, n7),
+            (n5, n8),
+        ]);
+
+        let queries = [(n7, n8), (n4, n8), (n6, n7), (n4, n5)];
+        let answers = lca_all(&map, n1, &queries);
+
+        assert_eq!(vec![Some(n5), Some(n2), Some(n1), Some(n2)], answers);
+    }
+
+    /// Tests that `lca_all` does not recurse forever on a graph with a
+    /// cycle reachable from `root`, and still answers queries between
+    /// nodes that do get fully processed.
+    #[test]
+    fn testlcaall_cycle() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+
+        map.extend_with_edges(&[(n1, n2), (n2, n3), (n3, n2)]);
+
+        let queries = [(n2, n3)];
+        let answers = lca_all(&map, n1, &queries);
+
+        assert_eq!(vec![Some(n2)], answers);
+    }
+
+    /// Tests that `lca_all` gives the same answer as `lca` on a DAG with a
+    /// diamond, where a node has more than one parent but the graph has
+    /// no cycle: `n1->n2, n1->n3, n2->n4, n3->n4, n3->n5`, `n4` shared by
+    /// `n2` and `n3`.
+    #[test]
+    fn testlcaall_diamond() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+        let n4 = map.add_node("4");
+        let n5 = map.add_node("5");
+
+        map.extend_with_edges(&[(n1, n2), (n1, n3), (n2, n4), (n3, n4), (n3, n5)]);
+
+        let queries = [(n2, n5)];
+        let answers = lca_all(&map, n1, &queries);
+
+        assert_eq!(vec![Some(n1)], answers);
+        assert_eq!(Some(n1), lca(&map, n2, n5));
+    }
+
+    /// Tests that `lca_weighted` picks the lowest common ancestor with the
+    /// cheapest `u -> lca -> v` path when edges have real weights.
+    #[test]
+    fn testlcaweighted_normal() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+        let n4 = map.add_node("4");
+        let n5 = map.add_node("5");
+
+        map.add_edge(n1, n2, 2);
+        map.add_edge(n1, n3, 5);
+        map.add_edge(n2, n4, 1);
+        map.add_edge(n3, n4, 1);
+        map.add_edge(n4, n5, 3);
+
+        let cost = |edge: EdgeReference<i32>| *edge.weight() as u32;
+
+        assert_eq!(Some((n1, 7)), lca_weighted(&map, n2, n3, cost));
+        assert_eq!(Some((n4, 3)), lca_weighted(&map, n4, n5, cost));
+    }
+
+    /// Tests that `lca_checked` gives the same answers as `lca` once the
+    /// graph has been validated as acyclic.
+    #[test]
+    fn testlcachecked_normal() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+        let n4 = map.add_node("4");
+
+        map.extend_with_edges(&[(n1, n2), (n1, n3), (n2, n4), (n3, n4)]);
+
+        let topo = validate(&map).unwrap();
+
+        assert_eq!(Some(n1), lca_checked(&map, &topo, n2, n3));
+        assert_eq!(Some(n4), lca_checked(&map, &topo, n4, n4));
+    }
+
+    /// Tests that `validate` reports a cycle and that `lca` (unlike
+    /// `lca_checked`, which assumes a graph already proven acyclic)
+    /// returns `None` for nodes on a cyclic path.
+    #[test]
+    fn testlcachecked_cycle() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+
+        map.extend_with_edges(&[(n1, n2), (n2, n1)]);
+
+        assert!(validate(&map).is_err());
+        assert_eq!(false, lca(&map, n1, n2).is_some());
+    }
 }
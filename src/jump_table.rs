@@ -0,0 +1,223 @@
+extern crate petgraph;
+
+use self::petgraph::visit::{IntoNeighborsDirected, NodeCount, VisitMap, Visitable};
+use self::petgraph::Direction::Outgoing;
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Precomputes binary-lifting ancestor jump tables for a rooted tree,
+/// turning repeated ancestor and LCA queries into O(log n) operations.
+///
+/// `up[k][v]` is the 2^k-th ancestor of `v`, built from a single DFS from
+/// the root. This reuses the `depth`/ancestor intuition the plain
+/// recursive `lca` already relies on, but makes it reusable across many
+/// queries instead of walking the full ancestor chain every time.
+///
+/// Generic over petgraph's visitor traits rather than a concrete `Graph`,
+/// so it also runs on `StableGraph`, `GraphMap` and other graph adaptors.
+pub struct JumpTable<Id> {
+    depth: HashMap<Id, u32>,
+    up: Vec<HashMap<Id, Id>>,
+    levels: usize,
+}
+
+impl<Id: Eq + Hash + Copy> JumpTable<Id> {
+    /// Builds the jump table for `graph` rooted at `root`.
+    ///
+    /// * `graph` - Graph that the jump table is built over.
+    /// * `root`  - The root node of the tree.
+    pub fn new<G>(graph: G, root: G::NodeId) -> JumpTable<G::NodeId>
+    where
+        G: IntoNeighborsDirected<NodeId = Id> + NodeCount + Visitable,
+    {
+        let node_count = graph.node_count().max(1);
+        let levels = (64 - (node_count as u64).leading_zeros()) as usize + 1;
+
+        let mut depth = HashMap::new();
+        let mut parent = HashMap::new();
+        let mut visited = graph.visit_map();
+        dfs_assign(graph, root, 0, &mut depth, &mut parent, &mut visited);
+
+        let mut up: Vec<HashMap<Id, Id>> = Vec::with_capacity(levels);
+        up.push(parent);
+        for k in 1..levels {
+            let mut table = HashMap::new();
+            for &node in depth.keys() {
+                if let Some(&mid) = up[k - 1].get(&node) {
+                    if let Some(&ancestor) = up[k - 1].get(&mid) {
+                        table.insert(node, ancestor);
+                    }
+                }
+            }
+            up.push(table);
+        }
+
+        JumpTable { depth, up, levels }
+    }
+
+    /// Returns the `k`-th ancestor of `v` by decomposing `k` into set bits
+    /// and hopping, or `None` if `v` is not in the tree or has fewer than
+    /// `k` ancestors.
+    pub fn kth_ancestor(&self, v: Id, k: u32) -> Option<Id> {
+        self.depth.get(&v)?;
+        let mut current = v;
+        let mut remaining = k;
+        let mut level = 0;
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                current = *self.up.get(level)?.get(&current)?;
+            }
+            remaining >>= 1;
+            level += 1;
+        }
+        Some(current)
+    }
+
+    /// Returns the lowest common ancestor of `u` and `v` by lifting the
+    /// deeper node up to equal depth, then jumping both nodes up in
+    /// decreasing powers of two while their ancestors differ.
+    pub fn lca(&self, u: Id, v: Id) -> Option<Id> {
+        let &du = self.depth.get(&u)?;
+        let &dv = self.depth.get(&v)?;
+        let (mut deeper, mut shallower) = if du >= dv { (u, v) } else { (v, u) };
+        let diff = if du >= dv { du - dv } else { dv - du };
+        deeper = self.kth_ancestor(deeper, diff)?;
+
+        if deeper == shallower {
+            return Some(deeper);
+        }
+
+        for level in (0..self.levels).rev() {
+            let deeper_up = self.up[level].get(&deeper).copied();
+            let shallower_up = self.up[level].get(&shallower).copied();
+            if let (Some(d), Some(s)) = (deeper_up, shallower_up) {
+                if d != s {
+                    deeper = d;
+                    shallower = s;
+                }
+            }
+        }
+
+        self.up[0].get(&deeper).copied()
+    }
+
+    /// Returns the number of edges on the path between `u` and `v`, via
+    /// `depth[u] + depth[v] - 2*depth[lca]`.
+    pub fn distance(&self, u: Id, v: Id) -> Option<u32> {
+        let &du = self.depth.get(&u)?;
+        let &dv = self.depth.get(&v)?;
+        let lca = self.lca(u, v)?;
+        let &dl = self.depth.get(&lca)?;
+        Some(du + dv - 2 * dl)
+    }
+}
+
+/// Records `depth` and direct `parent` for every node reachable from
+/// `node` via a DFS over `Outgoing` edges.
+///
+/// `visited` guards against a cycle reachable from `node` turning this
+/// into unbounded recursion, skipping any child already assigned.
+fn dfs_assign<G>(
+    graph: G,
+    node: G::NodeId,
+    depth: u32,
+    depths: &mut HashMap<G::NodeId, u32>,
+    parent: &mut HashMap<G::NodeId, G::NodeId>,
+    visited: &mut G::Map,
+) where
+    G: IntoNeighborsDirected + Visitable,
+    G::NodeId: Eq + Hash,
+{
+    if visited.is_visited(&node) {
+        return;
+    }
+    visited.visit(node);
+
+    depths.insert(node, depth);
+    for child in graph.neighbors_directed(node, Outgoing) {
+        if !visited.is_visited(&child) {
+            parent.insert(child, node);
+        }
+        dfs_assign(graph, child, depth + 1, depths, parent, visited);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate petgraph;
+
+    use self::petgraph::graph::Graph;
+    use super::JumpTable;
+
+    /// Tests `kth_ancestor`, `lca` and `distance` on a small tree.
+    #[test]
+    fn testjumptable_normal() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+        let n4 = map.add_node("4");
+        let n5 = map.add_node("5");
+        let n6 = map.add_node("6");
+        let n7 = map.add_node("7");
+        let n8 = map.add_node("8");
+
+        map.extend_with_edges(&[
+            (n1, n2),
+            (n1, n3),
+            (n2, n4),
+            (n2, n5),
+            (n3, n6),
+            (n5, n7),
+            (n5, n8),
+        ]);
+
+        let table = JumpTable::new(&map, n1);
+
+        assert_eq!(Some(n5), table.kth_ancestor(n7, 1));
+        assert_eq!(Some(n2), table.kth_ancestor(n7, 2));
+        assert_eq!(Some(n1), table.kth_ancestor(n7, 3));
+        assert_eq!(None, table.kth_ancestor(n7, 4));
+
+        assert_eq!(Some(n5), table.lca(n7, n8));
+        assert_eq!(Some(n2), table.lca(n4, n8));
+        assert_eq!(Some(n1), table.lca(n6, n7));
+
+        assert_eq!(Some(2), table.distance(n7, n8));
+        assert_eq!(Some(3), table.distance(n4, n8));
+        assert_eq!(Some(5), table.distance(n6, n7));
+    }
+
+    /// Tests that `kth_ancestor` returns `None` for a node outside the
+    /// tree the `JumpTable` was built from, even for `k == 0`.
+    #[test]
+    fn testjumptable_unreachable() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+
+        map.extend_with_edges(&[(n1, n2)]);
+
+        let table = JumpTable::new(&map, n1);
+
+        assert_eq!(None, table.kth_ancestor(n3, 0));
+    }
+
+    /// Tests that building a `JumpTable` does not recurse forever on a
+    /// graph with a cycle reachable from `root`.
+    #[test]
+    fn testjumptable_cycle() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+
+        map.extend_with_edges(&[(n1, n2), (n2, n3), (n3, n2)]);
+
+        let table = JumpTable::new(&map, n1);
+
+        assert_eq!(Some(n1), table.kth_ancestor(n1, 0));
+    }
+}
@@ -0,0 +1,214 @@
+extern crate petgraph;
+
+use self::petgraph::visit::{IntoNeighborsDirected, VisitMap, Visitable};
+use self::petgraph::Direction::Outgoing;
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Preprocesses a rooted tree once so that repeated lowest-common-ancestor
+/// queries can be answered in O(1) each.
+///
+/// Built from an Euler tour of the tree plus a sparse table for
+/// range-minimum queries over node depth, following the classic
+/// Euler-tour + RMQ reduction of LCA. This complements the one-shot `lca`
+/// function: preprocessing is O(n log n), but every `query` call after
+/// that is O(1), which wins over `lca`'s recursive ancestor walk for
+/// workloads that query the same tree many times.
+///
+/// Generic over petgraph's visitor traits rather than a concrete `Graph`,
+/// so it indexes `StableGraph`, `GraphMap` and other graph adaptors too.
+pub struct LcaIndex<Id> {
+    euler: Vec<Id>,
+    depth: Vec<u32>,
+    first_occurrence: HashMap<Id, usize>,
+    sparse: Vec<Vec<usize>>,
+}
+
+impl<Id: Eq + Hash + Copy> LcaIndex<Id> {
+    /// Builds the index for `graph` rooted at `root`.
+    ///
+    /// * `graph` - Graph that the lowest common ancestor is applied on.
+    /// * `root`  - The root node of the tree to index.
+    pub fn new<G>(graph: G, root: G::NodeId) -> LcaIndex<G::NodeId>
+    where
+        G: IntoNeighborsDirected<NodeId = Id> + Visitable,
+    {
+        let mut euler = Vec::new();
+        let mut depth = Vec::new();
+        let mut first_occurrence = HashMap::new();
+        let mut visited = graph.visit_map();
+
+        euler_tour(
+            graph,
+            root,
+            0,
+            &mut euler,
+            &mut depth,
+            &mut first_occurrence,
+            &mut visited,
+        );
+        let sparse = build_sparse_table(&depth);
+
+        LcaIndex {
+            euler,
+            depth,
+            first_occurrence,
+            sparse,
+        }
+    }
+
+    /// Returns the lowest common ancestor of `u` and `v`, or `None` if
+    /// either node was not reachable from the root the index was built
+    /// with.
+    pub fn query(&self, u: Id, v: Id) -> Option<Id> {
+        let &fu = self.first_occurrence.get(&u)?;
+        let &fv = self.first_occurrence.get(&v)?;
+        let l = fu.min(fv);
+        let r = fu.max(fv);
+        Some(self.euler[self.range_min_pos(l, r)])
+    }
+
+    /// Returns the Euler-tour position of the minimum-depth entry in
+    /// `depth[l..=r]`, using the precomputed sparse table.
+    fn range_min_pos(&self, l: usize, r: usize) -> usize {
+        let len = r - l + 1;
+        let k = (63 - (len as u64).leading_zeros()) as usize;
+        let left = self.sparse[k][l];
+        let right = self.sparse[k][r + 1 - (1 << k)];
+        if self.depth[left] <= self.depth[right] {
+            left
+        } else {
+            right
+        }
+    }
+}
+
+/// Records an Euler tour of `graph` from `node` at `depth`, pushing the
+/// node onto entry and after returning from each child, and noting each
+/// node's first tour position in `first_occurrence`.
+///
+/// `visited` guards against a cycle reachable from `node` turning this
+/// into unbounded recursion, skipping any child already on the tour.
+fn euler_tour<G>(
+    graph: G,
+    node: G::NodeId,
+    depth: u32,
+    euler: &mut Vec<G::NodeId>,
+    depths: &mut Vec<u32>,
+    first_occurrence: &mut HashMap<G::NodeId, usize>,
+    visited: &mut G::Map,
+) where
+    G: IntoNeighborsDirected + Visitable,
+    G::NodeId: Eq + Hash,
+{
+    if visited.is_visited(&node) {
+        return;
+    }
+    visited.visit(node);
+
+    first_occurrence.entry(node).or_insert(euler.len());
+    euler.push(node);
+    depths.push(depth);
+
+    for child in graph.neighbors_directed(node, Outgoing) {
+        euler_tour(graph, child, depth + 1, euler, depths, first_occurrence, visited);
+        euler.push(node);
+        depths.push(depth);
+    }
+}
+
+/// Builds a sparse table over `depth` where `sparse[k][i]` is the index of
+/// the minimum-depth entry in the range `[i, i + 2^k)`.
+fn build_sparse_table(depth: &[u32]) -> Vec<Vec<usize>> {
+    let n = depth.len();
+    if n == 0 {
+        return vec![Vec::new()];
+    }
+    let levels = (63 - (n as u64).leading_zeros()) as usize + 1;
+    let mut sparse = vec![vec![0usize; n]; levels];
+    for i in 0..n {
+        sparse[0][i] = i;
+    }
+    for k in 1..levels {
+        let half = 1 << (k - 1);
+        for i in 0..=(n - (1 << k)) {
+            let left = sparse[k - 1][i];
+            let right = sparse[k - 1][i + half];
+            sparse[k][i] = if depth[left] <= depth[right] { left } else { right };
+        }
+    }
+    sparse
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate petgraph;
+
+    use super::LcaIndex;
+    use self::petgraph::graph::Graph;
+
+    /// Tests that `LcaIndex` answers repeated queries on a tree with the
+    /// same results as calling `lca` for each pair.
+    #[test]
+    fn testlcaindex_normal() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+        let n4 = map.add_node("4");
+        let n5 = map.add_node("5");
+        let n6 = map.add_node("6");
+        let n7 = map.add_node("7");
+        let n8 = map.add_node("8");
+
+        map.extend_with_edges(&[
+            (n1, n2),
+            (n1, n3),
+            (n2, n4),
+            (n2, n5),
+            (n3, n6),
+            (n5, n7),
+            (n5, n8),
+        ]);
+
+        let index = LcaIndex::new(&map, n1);
+
+        assert_eq!(Some(n5), index.query(n7, n8));
+        assert_eq!(Some(n2), index.query(n4, n8));
+        assert_eq!(Some(n1), index.query(n6, n7));
+        assert_eq!(Some(n1), index.query(n1, n1));
+    }
+
+    /// Tests that `query` returns `None` for a node outside the tree the
+    /// index was built from.
+    #[test]
+    fn testlcaindex_unreachable() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+
+        map.extend_with_edges(&[(n1, n2)]);
+
+        let index = LcaIndex::new(&map, n1);
+
+        assert_eq!(None, index.query(n2, n3));
+    }
+
+    /// Tests that building an `LcaIndex` does not recurse forever on a
+    /// graph with a cycle reachable from `root`.
+    #[test]
+    fn testlcaindex_cycle() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+
+        map.extend_with_edges(&[(n1, n2), (n2, n3), (n3, n2)]);
+
+        let index = LcaIndex::new(&map, n1);
+
+        assert_eq!(Some(n1), index.query(n1, n1));
+    }
+}
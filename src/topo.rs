@@ -0,0 +1,130 @@
+extern crate petgraph;
+
+use self::petgraph::visit::{IntoNeighborsDirected, IntoNodeIdentifiers};
+use self::petgraph::Direction::{Incoming, Outgoing};
+
+use std::collections::HashMap;
+use std::collections::LinkedList;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+/// A topological ordering of a graph's nodes, produced once by `validate`
+/// and reusable across many queries.
+pub struct TopoOrder<Id> {
+    order: Vec<Id>,
+    position: HashMap<Id, usize>,
+}
+
+impl<Id: Eq + Hash + Copy> TopoOrder<Id> {
+    /// Returns the nodes in topological order.
+    pub fn order(&self) -> &[Id] {
+        &self.order
+    }
+
+    /// Returns `node`'s position in the topological order, or `None` if
+    /// `node` was not part of the graph the order was built from.
+    pub fn position(&self, node: Id) -> Option<usize> {
+        self.position.get(&node).copied()
+    }
+}
+
+/// Returned by `validate` when the graph contains a cycle, so it has no
+/// topological order.
+#[derive(Debug, PartialEq)]
+pub struct CycleError;
+
+impl fmt::Display for CycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "graph contains a cycle")
+    }
+}
+
+impl Error for CycleError {}
+
+/// Validates that `graph` is acyclic and produces a topological ordering,
+/// using Kahn's algorithm: repeatedly emit nodes with in-degree zero and
+/// decrement their successors' in-degrees. A non-empty remainder once no
+/// more nodes can be emitted means a cycle, mirroring petgraph's
+/// `toposort`/`is_cyclic_directed`.
+///
+/// Unlike `check_cycle`, which re-walks the whole ancestor chain on every
+/// call, this pays the cycle-detection cost once; the resulting
+/// `TopoOrder` can then be reused by `lca_checked` for repeated queries
+/// on the same graph.
+pub fn validate<G>(graph: G) -> Result<TopoOrder<G::NodeId>, CycleError>
+where
+    G: IntoNodeIdentifiers + IntoNeighborsDirected,
+    G::NodeId: Eq + Hash + Copy,
+{
+    let mut in_degree: HashMap<G::NodeId, usize> = HashMap::new();
+    for node in graph.node_identifiers() {
+        let degree = graph.neighbors_directed(node, Incoming).count();
+        in_degree.insert(node, degree);
+    }
+
+    let mut queue: LinkedList<G::NodeId> = in_degree
+        .iter()
+        .filter(|&(_, &degree)| degree == 0)
+        .map(|(&node, _)| node)
+        .collect();
+
+    let mut order = Vec::new();
+    let mut position = HashMap::new();
+
+    while let Some(node) = queue.pop_front() {
+        position.insert(node, order.len());
+        order.push(node);
+
+        for successor in graph.neighbors_directed(node, Outgoing) {
+            let degree = in_degree.get_mut(&successor).unwrap();
+            *degree -= 1;
+            if *degree == 0 {
+                queue.push_back(successor);
+            }
+        }
+    }
+
+    if order.len() != in_degree.len() {
+        return Err(CycleError);
+    }
+
+    Ok(TopoOrder { order, position })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate petgraph;
+
+    use self::petgraph::graph::Graph;
+    use super::validate;
+
+    /// Tests that `validate` produces a topological order on a DAG.
+    #[test]
+    fn testvalidate_acyclic() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+        let n3 = map.add_node("3");
+
+        map.extend_with_edges(&[(n1, n2), (n2, n3)]);
+
+        let topo = validate(&map).unwrap();
+        assert_eq!(topo.position(n1), Some(0));
+        assert_eq!(topo.position(n2), Some(1));
+        assert_eq!(topo.position(n3), Some(2));
+    }
+
+    /// Tests that `validate` reports a `CycleError` when the graph has a
+    /// cycle.
+    #[test]
+    fn testvalidate_cycle() {
+        let mut map = Graph::<&str, i32>::new();
+        let n1 = map.add_node("1");
+        let n2 = map.add_node("2");
+
+        map.extend_with_edges(&[(n1, n2), (n2, n1)]);
+
+        assert!(validate(&map).is_err());
+    }
+}
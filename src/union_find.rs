@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A disjoint-set (union-find) structure with union by rank and path
+/// compression, plus a per-set "ancestor" label.
+///
+/// This mirrors the disjoint-set idea petgraph uses internally for
+/// `connected_components`, but additionally tracks an `ancestor` for each
+/// set's representative so algorithms such as Tarjan's offline LCA can
+/// record "the node that currently stands in for this set" as sets are
+/// merged.
+pub struct UnionFind<T: Eq + Hash + Copy> {
+    parent: HashMap<T, T>,
+    rank: HashMap<T, u32>,
+    ancestor: HashMap<T, T>,
+}
+
+impl<T: Eq + Hash + Copy> UnionFind<T> {
+    /// Creates an empty union-find structure.
+    pub fn new() -> Self {
+        UnionFind {
+            parent: HashMap::new(),
+            rank: HashMap::new(),
+            ancestor: HashMap::new(),
+        }
+    }
+
+    /// Makes `x` its own set, with itself as ancestor. Has no effect if
+    /// `x` is already in the structure.
+    pub fn make_set(&mut self, x: T) {
+        self.parent.entry(x).or_insert(x);
+        self.rank.entry(x).or_insert(0);
+        self.ancestor.entry(x).or_insert(x);
+    }
+
+    /// Finds the representative of the set containing `x`, compressing
+    /// the path as it walks up.
+    pub fn find(&mut self, x: T) -> T {
+        let parent = *self.parent.get(&x).unwrap_or(&x);
+        if parent == x {
+            return x;
+        }
+        let root = self.find(parent);
+        self.parent.insert(x, root);
+        root
+    }
+
+    /// Unions the sets containing `x` and `y`, using union by rank.
+    /// Returns the representative of the merged set.
+    pub fn union(&mut self, x: T, y: T) -> T {
+        let x_root = self.find(x);
+        let y_root = self.find(y);
+        if x_root == y_root {
+            return x_root;
+        }
+        let x_rank = *self.rank.get(&x_root).unwrap_or(&0);
+        let y_rank = *self.rank.get(&y_root).unwrap_or(&0);
+        if x_rank < y_rank {
+            self.parent.insert(x_root, y_root);
+            y_root
+        } else if x_rank > y_rank {
+            self.parent.insert(y_root, x_root);
+            x_root
+        } else {
+            self.parent.insert(y_root, x_root);
+            self.rank.insert(x_root, x_rank + 1);
+            x_root
+        }
+    }
+
+    /// Sets the ancestor label of the set containing `x`.
+    pub fn set_ancestor(&mut self, x: T, ancestor: T) {
+        let root = self.find(x);
+        self.ancestor.insert(root, ancestor);
+    }
+
+    /// Returns the ancestor label of the set containing `x`.
+    pub fn ancestor(&mut self, x: T) -> T {
+        let root = self.find(x);
+        *self.ancestor.get(&root).unwrap_or(&root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnionFind;
+
+    #[test]
+    fn testunionfind_basic() {
+        let mut uf = UnionFind::<u32>::new();
+        uf.make_set(1);
+        uf.make_set(2);
+        uf.make_set(3);
+
+        assert_eq!(1, uf.find(1));
+        assert_eq!(2, uf.find(2));
+
+        uf.union(1, 2);
+        assert_eq!(uf.find(1), uf.find(2));
+
+        uf.set_ancestor(1, 3);
+        assert_eq!(3, uf.ancestor(2));
+    }
+}